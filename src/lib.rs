@@ -1,13 +1,26 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 mod bf_tape {
+    use alloc::boxed::Box;
+    use alloc::collections::BTreeMap;
+    use alloc::format;
+    use alloc::string::String;
+    use alloc::vec::Vec;
 
-    pub struct Tape<T> {
+    /// The program tape: a contiguous, forward-scanning sequence of
+    /// instructions. Unlike the memory tape, a loaded program has a fixed
+    /// length and is only ever read left to right (plus jumps within that
+    /// range), so a plain growable buffer is all it needs.
+    pub struct ProgramTape<T> {
         data: Vec<T>,
         ptr: usize,
     }
 
-    impl<T: Copy + Clone + Default> Tape<T> {
+    impl<T: Copy + Clone + Default> ProgramTape<T> {
         fn allocate_for_ptr(&mut self, ptr_pos: usize) {
-            // Ensure Tape.data has at least the size of ptr, resizing if necessary.
+            // Ensure ProgramTape.data has at least the size of ptr, resizing if necessary.
             // This function allocates for pos + 1000 to avoid excessive calls to Vec.resize().
             if self.data.len() < ptr_pos + 1 {
                 self.data.resize(ptr_pos + 1000, T::default());
@@ -19,8 +32,8 @@ mod bf_tape {
             self.data[self.ptr]
         }
 
-        pub fn new() -> Tape<T> {
-            let mut tape = Tape {
+        pub fn new() -> ProgramTape<T> {
+            let mut tape = ProgramTape {
                 data: Vec::new(),
                 ptr: 0,
             };
@@ -38,6 +51,14 @@ mod bf_tape {
             self.ptr
         }
 
+        pub fn len(&self) -> usize {
+            self.data.len()
+        }
+
+        pub fn peek_at(&self, pos: usize) -> T {
+            self.data[pos]
+        }
+
         pub fn seek(&mut self, offset: i32) -> Result<(), &str> {
             let new_ptr_pos = self.ptr as i32 + offset;
             if new_ptr_pos < 0 {
@@ -60,9 +81,9 @@ mod bf_tape {
         }
     }
 
-    impl<T: Copy + Clone + Default> FromIterator<T> for Tape<T> {
+    impl<T: Copy + Clone + Default> FromIterator<T> for ProgramTape<T> {
         fn from_iter<A: IntoIterator<Item = T>>(iter: A) -> Self {
-            let mut tape = Tape::<T>::new();
+            let mut tape = ProgramTape::<T>::new();
             for i in iter {
                 tape.set(i);
                 tape.seek(1).unwrap();
@@ -71,24 +92,164 @@ mod bf_tape {
             tape
         }
     }
+
+    /// Cells per chunk in `Tape`'s sparse backing store.
+    const CHUNK_SIZE: usize = 4096;
+
+    /// What `Tape::seek` should do when a cell-count limit is set and the
+    /// head would move past either end.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum OverflowPolicy {
+        /// Reject the move and leave the head where it was.
+        Error,
+        /// Wrap the head back round to the other end, as on classic fixed-
+        /// size BF tapes.
+        Wrap,
+    }
+
+    /// The memory tape: by default conceptually infinite in both
+    /// directions, but optionally bounded to a fixed cell count (mirroring
+    /// classic BF environments, e.g. `Tape::with_limit(30_000)`) with a
+    /// chosen `OverflowPolicy` for what happens when the head would move
+    /// past an end. Cells are grouped into fixed-size chunks allocated
+    /// lazily on first write, keyed by chunk index, so memory use stays
+    /// proportional to the cells a program actually touches rather than
+    /// to how far the head has travelled. A never-touched cell reads as
+    /// `T::default()`.
+    pub struct Tape<T> {
+        chunks: BTreeMap<isize, Box<[T; CHUNK_SIZE]>>,
+        ptr: isize,
+        limit: Option<usize>,
+        overflow: OverflowPolicy,
+    }
+
+    impl<T: Copy + Clone + Default> Default for Tape<T> {
+        fn default() -> Self {
+            Tape::new()
+        }
+    }
+
+    impl<T: Copy + Clone + Default> Tape<T> {
+        fn chunk_and_offset(pos: isize) -> (isize, usize) {
+            (
+                pos.div_euclid(CHUNK_SIZE as isize),
+                pos.rem_euclid(CHUNK_SIZE as isize) as usize,
+            )
+        }
+
+        pub fn new() -> Tape<T> {
+            Tape {
+                chunks: BTreeMap::new(),
+                ptr: 0,
+                limit: None,
+                overflow: OverflowPolicy::Error,
+            }
+        }
+
+        /// A tape bounded to `limit` cells (positions `0..limit`), erroring
+        /// on overflow. Use `with_limit_and_policy` to wrap instead.
+        pub fn with_limit(limit: usize) -> Tape<T> {
+            Tape::with_limit_and_policy(limit, OverflowPolicy::Error)
+        }
+
+        pub fn with_limit_and_policy(limit: usize, overflow: OverflowPolicy) -> Tape<T> {
+            assert!(limit > 0, "Tape limit must be at least 1 cell");
+            Tape {
+                chunks: BTreeMap::new(),
+                ptr: 0,
+                limit: Some(limit),
+                overflow,
+            }
+        }
+
+        pub fn get(&self) -> T {
+            let (chunk, offset) = Self::chunk_and_offset(self.ptr);
+            self.chunks
+                .get(&chunk)
+                .map(|cells| cells[offset])
+                .unwrap_or_default()
+        }
+
+        pub fn set(&mut self, elem: T) {
+            let (chunk, offset) = Self::chunk_and_offset(self.ptr);
+            let cells = self
+                .chunks
+                .entry(chunk)
+                .or_insert_with(|| Box::new([T::default(); CHUNK_SIZE]));
+            cells[offset] = elem;
+        }
+
+        pub fn seek(&mut self, offset: i32) -> Result<(), String> {
+            let new_ptr = self.ptr + offset as isize;
+            let Some(limit) = self.limit else {
+                self.ptr = new_ptr;
+                return Ok(());
+            };
+            let limit = limit as isize;
+            if new_ptr >= 0 && new_ptr < limit {
+                self.ptr = new_ptr;
+                return Ok(());
+            }
+            match self.overflow {
+                OverflowPolicy::Error => {
+                    Err(format!("Tape head moved outside the {}-cell bound", limit))
+                }
+                OverflowPolicy::Wrap => {
+                    self.ptr = new_ptr.rem_euclid(limit);
+                    Ok(())
+                }
+            }
+        }
+    }
 }
 
 pub mod brainfuck {
-    use crate::bf_tape::Tape;
-    use std::fs;
+    use crate::bf_tape::ProgramTape;
+    pub use crate::bf_tape::{OverflowPolicy, Tape};
+    use alloc::format;
+    use alloc::string::{String, ToString};
+    use alloc::{vec, vec::Vec};
+
+    #[cfg(feature = "std")]
     use std::io;
-    use std::io::Read;
-    use std::io::Write;
+    #[cfg(feature = "std")]
+    pub use std::io::{Read, Write};
+
+    /// Error returned by the `no_std` `Read`/`Write` stand-ins below.
+    #[cfg(not(feature = "std"))]
+    #[derive(Debug)]
+    pub struct IoError;
 
-    pub fn load_program(file: &String) -> Result<Tape<char>, String> {
+    /// `core`-compatible stand-in for `std::io::Read`, used when the `std`
+    /// feature is disabled. Mirrors just the bit of the API the interpreter
+    /// needs to read a `,` byte from a caller-supplied source.
+    #[cfg(not(feature = "std"))]
+    pub trait Read {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError>;
+    }
+
+    /// `core`-compatible stand-in for `std::io::Write`, used when the `std`
+    /// feature is disabled. Mirrors just the bit of the API the interpreter
+    /// needs to write a `.` byte to a caller-supplied sink.
+    #[cfg(not(feature = "std"))]
+    pub trait Write {
+        fn write_all(&mut self, buf: &[u8]) -> Result<(), IoError>;
+        fn flush(&mut self) -> Result<(), IoError>;
+    }
+
+    /// Load a program from a file. Requires the `std` feature (it shells
+    /// out to `std::fs`); embedders without `std` should collect a
+    /// `ProgramTape` from their own source bytes instead.
+    #[cfg(feature = "std")]
+    pub fn load_program(file: &String) -> Result<ProgramTape<char>, String> {
         // Load program from file or stdin, return program as a Tape of
         // valid brainfuck symbols.
 
         // Get program
-        let mut input = match fs::read_to_string(file) {
+        let mut input = match std::fs::read_to_string(file) {
             Ok(input) => input,
             Err(err) => {
-                return Err(format!("Couldn't read file: {}", err.to_string()));
+                return Err(format!("Couldn't read file: {}", err));
             }
         };
 
@@ -99,104 +260,317 @@ pub mod brainfuck {
         Ok(input.chars().collect())
     }
 
-    pub fn run_program(mut program: Tape<char>) -> Result<(), &'static str> {
-        let mut memory = Tape::<u8>::new();
-        let mut bracket_stack = Vec::<usize>::new();
-        loop {
-            match program.next() {
+    /// The outcome of executing a single instruction via `Interpreter::step`.
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum StepStatus {
+        /// An instruction ran and the interpreter is ready for the next one.
+        Running,
+        /// The program pointer reached the end of the tape.
+        Halted,
+        /// A `,` was just executed; a byte was read from the input reader.
+        NeedsInput,
+        /// A `.` was just executed; this is the byte it wrote.
+        ProducedOutput(u8),
+    }
+
+    /// A brainfuck program bound to its memory tape and its input/output
+    /// streams, steppable one instruction at a time. This is what
+    /// `run_program` drives to completion, but callers can also step it
+    /// themselves to build debuggers, breakpoints, or single-step
+    /// visualizers, and can supply any `Read`/`Write` pair instead of
+    /// stdio to run a program's I/O through in-memory buffers.
+    pub struct Interpreter<R: Read, W: Write> {
+        program: ProgramTape<char>,
+        memory: Tape<u8>,
+        jump_table: Vec<usize>,
+        input: R,
+        output: W,
+    }
+
+    impl<R: Read, W: Write> Interpreter<R, W> {
+        /// Build an interpreter with a fresh, unbounded memory tape.
+        pub fn new(program: ProgramTape<char>, input: R, output: W) -> Result<Interpreter<R, W>, String> {
+            Interpreter::with_memory(program, Tape::<u8>::new(), input, output)
+        }
+
+        /// Build an interpreter against a caller-supplied memory tape, e.g.
+        /// one bounded with `Tape::with_limit` for parity with classic BF
+        /// environments that fix the tape at a set size.
+        pub fn with_memory(
+            program: ProgramTape<char>,
+            memory: Tape<u8>,
+            input: R,
+            output: W,
+        ) -> Result<Interpreter<R, W>, String> {
+            let jump_table = build_jump_table(&program)?;
+            Ok(Interpreter {
+                program,
+                memory,
+                jump_table,
+                input,
+                output,
+            })
+        }
+
+        /// Execute exactly one instruction and report what it did.
+        pub fn step(&mut self) -> Result<StepStatus, String> {
+            match self.program.next() {
                 '>' => {
-                    memory.seek(1).unwrap();
+                    self.memory.seek(1)?;
+                    Ok(StepStatus::Running)
                 }
                 '<' => {
-                    memory.seek(-1).unwrap();
+                    self.memory.seek(-1)?;
+                    Ok(StepStatus::Running)
                 }
                 '+' => {
-                    memory.set(memory.get().wrapping_add(1));
+                    self.memory.set(self.memory.get().wrapping_add(1));
+                    Ok(StepStatus::Running)
                 }
                 '-' => {
-                    memory.set(memory.get().wrapping_add_signed(-1));
+                    self.memory.set(self.memory.get().wrapping_add_signed(-1));
+                    Ok(StepStatus::Running)
                 }
                 '.' => {
-                    print_byte_to_stdout(memory.get()).unwrap();
+                    let byte = self.memory.get();
+                    self.output
+                        .write_all(&[byte])
+                        .and_then(|_| self.output.flush())
+                        .map_err(|err| format!("Couldn't write to output: {:?}", err))?;
+                    Ok(StepStatus::ProducedOutput(byte))
                 }
                 ',' => {
-                    memory.set(get_byte_from_stdin().expect("Couldn't read from stdin"));
+                    let mut buf = [0u8; 1];
+                    let byte = match self
+                        .input
+                        .read(&mut buf)
+                        .map_err(|err| format!("Couldn't read from input: {:?}", err))?
+                    {
+                        0 => 0u8,
+                        _ => buf[0],
+                    };
+                    self.memory.set(byte);
+                    Ok(StepStatus::NeedsInput)
                 }
                 '[' => {
-                    left_bracket(&mut program, &memory, &mut bracket_stack).unwrap();
+                    // jump_table[pos] points at the matching ], so jumping
+                    // one past it re-enters right where the loop body ends.
+                    if self.memory.get() == 0 {
+                        self.jump_to(self.jump_table[self.program.pos() - 1] + 1);
+                    }
+                    Ok(StepStatus::Running)
                 }
                 ']' => {
-                    right_bracket(&mut program, &memory, &mut bracket_stack).unwrap();
-                }
-                '\0' => return Ok(()), // End of tape
-                _ => {
-                    return Err("Invalid program symbol.");
+                    // jump_table[pos] points at the matching [, so jumping
+                    // one past it re-enters right after the loop header.
+                    if self.memory.get() != 0 {
+                        self.jump_to(self.jump_table[self.program.pos() - 1] + 1);
+                    }
+                    Ok(StepStatus::Running)
                 }
+                '\0' => Ok(StepStatus::Halted), // End of tape
+                _ => Err("Invalid program symbol.".to_string()),
             }
         }
-    }
 
-    fn get_byte_from_stdin() -> Result<u8, io::Error> {
-        if let Some(byte) = io::stdin().bytes().next() {
-            byte
-        } else {
-            Ok(0u8)
+        fn jump_to(&mut self, target: usize) {
+            let offset = target as i32 - self.program.pos() as i32;
+            self.program.seek(offset).expect("Couldn't seek program tape.");
         }
-    }
 
-    fn left_bracket(
-        program: &mut Tape<char>,
-        memory: &Tape<u8>,
-        bracket_stack: &mut Vec<usize>,
-    ) -> Result<(), &'static str> {
-        // Implement the BF [ command. If returns Err if no ] is found.
-        let orig_stack_len = bracket_stack.len();
-        bracket_stack.push(program.pos());
-        if memory.get() != 0 {
-            return Ok(());
+        /// Run `step()` until the next `,`/`.` or halt, returning whichever
+        /// status interrupted the run.
+        pub fn advance_until_io(&mut self) -> Result<StepStatus, String> {
+            loop {
+                match self.step()? {
+                    StepStatus::Running => continue,
+                    status => return Ok(status),
+                }
+            }
         }
-        loop {
-            match program.next() {
-                '[' => {
-                    bracket_stack.push(program.pos());
+
+        /// Run the program to completion.
+        pub fn run(&mut self) -> Result<(), String> {
+            loop {
+                if let StepStatus::Halted = self.step()? {
+                    return Ok(());
                 }
+            }
+        }
+    }
+
+    /// Pre-pass the program once, matching every `[` to its `]` so that
+    /// branches become an O(1) jump instead of a runtime rescan. The
+    /// result is indexed by position, with `jump_table[open] == close`
+    /// and `jump_table[close] == open`; other positions are unused.
+    fn build_jump_table(program: &ProgramTape<char>) -> Result<Vec<usize>, String> {
+        let len = program.len();
+        let mut jump_table = vec![0usize; len];
+        let mut open_stack = Vec::<usize>::new();
+        for pos in 0..len {
+            match program.peek_at(pos) {
+                '[' => open_stack.push(pos),
                 ']' => {
-                    bracket_stack
+                    let open = open_stack
                         .pop()
-                        .expect("Unexpected empty bracket stack.");
-                    if bracket_stack.len() == orig_stack_len {
-                        // We've reached the matching ]
-                        return Ok(());
-                    }
+                        .ok_or_else(|| format!("Unmatched ']' at position {}", pos))?;
+                    jump_table[open] = pos;
+                    jump_table[pos] = open;
                 }
-                '\0' => return Err("Reached end of tape before finding matching ]"),
                 _ => (),
             }
         }
+        if let Some(open) = open_stack.first() {
+            return Err(format!("Unmatched '[' at position {}", open));
+        }
+        Ok(jump_table)
     }
 
-    fn print_byte_to_stdout(byte: u8) -> std::io::Result<()> {
-        io::stdout().write(&[byte])?;
-        io::stdout().flush()?;
-        Ok(())
+    /// Run a program against stdio. Requires the `std` feature; embedders
+    /// without `std` should drive `run_program_with_io` (or `Interpreter`
+    /// directly) with their own `Read`/`Write` implementations.
+    #[cfg(feature = "std")]
+    pub fn run_program(program: ProgramTape<char>) -> Result<(), String> {
+        run_program_with_io(program, io::stdin(), io::stdout())
     }
 
-    fn right_bracket(
-        program: &mut Tape<char>,
-        memory: &Tape<u8>,
-        bracket_stack: &mut Vec<usize>,
-    ) -> Result<(), &'static str> {
-        // Implement the BF ] command. If returns Err if no [ is found.
-        if bracket_stack.len() == 0 {
-            return Err("Encountered ] without matching [");
+    /// Like `run_program`, but reads `,` from `input` and writes `.` to
+    /// `output` instead of stdio. This is what makes the interpreter
+    /// deterministically testable and embeddable: tests can feed a byte
+    /// slice in and capture output into a `Vec<u8>`.
+    pub fn run_program_with_io<R: Read, W: Write>(
+        program: ProgramTape<char>,
+        input: R,
+        output: W,
+    ) -> Result<(), String> {
+        Interpreter::new(program, input, output)?.run()
+    }
+
+    // Every test here uses `&[u8]`/`Vec<u8>` as `Read`/`Write`, which only
+    // hold under the `std` feature's `pub use std::io::{Read, Write}`
+    // aliasing; the `no_std` stand-ins above have no impls for them.
+    #[cfg(all(test, feature = "std"))]
+    mod tests {
+        use super::*;
+
+        /// A `Write` that always fails, standing in for a socket or serial
+        /// port that can legitimately error on `.`.
+        struct FailingWriter;
+
+        impl Write for FailingWriter {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("write failed"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        #[test]
+        fn step_surfaces_output_errors_instead_of_panicking() {
+            let program: ProgramTape<char> = ".".chars().collect();
+            let mut interp = Interpreter::new(program, &b""[..], FailingWriter).unwrap();
+            assert!(interp.step().is_err());
+        }
+
+        #[test]
+        fn step_runs_then_halts_at_end_of_tape() {
+            let program: ProgramTape<char> = "+".chars().collect();
+            let mut interp = Interpreter::new(program, &b""[..], Vec::new()).unwrap();
+            assert_eq!(interp.step().unwrap(), StepStatus::Running);
+            assert_eq!(interp.step().unwrap(), StepStatus::Halted);
+        }
+
+        #[test]
+        fn advance_until_io_stops_at_produced_output_then_halts() {
+            let program: ProgramTape<char> = "+.".chars().collect();
+            let mut interp = Interpreter::new(program, &b""[..], Vec::new()).unwrap();
+            assert_eq!(
+                interp.advance_until_io().unwrap(),
+                StepStatus::ProducedOutput(1)
+            );
+            assert_eq!(interp.advance_until_io().unwrap(), StepStatus::Halted);
+        }
+
+        #[test]
+        fn advance_until_io_stops_at_needs_input() {
+            let program: ProgramTape<char> = ",".chars().collect();
+            let mut interp = Interpreter::new(program, &b"A"[..], Vec::new()).unwrap();
+            assert_eq!(interp.advance_until_io().unwrap(), StepStatus::NeedsInput);
+        }
+
+        #[test]
+        fn echoes_input_to_output_via_buffers() {
+            let program: ProgramTape<char> = ",.".chars().collect();
+            let mut output = Vec::new();
+            run_program_with_io(program, &b"A"[..], &mut output).unwrap();
+            assert_eq!(output, b"A");
         }
-        if memory.get() == 0 {
-            bracket_stack.pop();
-        } else {
-            program
-                .seek(*bracket_stack.last().unwrap() as i32 - program.pos() as i32)
-                .expect("Coudn't seek program tape.");
+
+        #[test]
+        fn runs_a_loop_via_the_jump_table() {
+            // ++++++++[>++++++++<-]>+. writes 65 ('A') via a loop.
+            let program: ProgramTape<char> = "++++++++[>++++++++<-]>+.".chars().collect();
+            let mut output = Vec::new();
+            run_program_with_io(program, &b""[..], &mut output).unwrap();
+            assert_eq!(output, b"A");
+        }
+
+        #[test]
+        fn unmatched_open_bracket_is_a_load_time_error() {
+            let program: ProgramTape<char> = "[+".chars().collect();
+            match Interpreter::new(program, &b""[..], Vec::new()) {
+                Err(err) => assert!(err.contains("Unmatched '['")),
+                Ok(_) => panic!("expected an unmatched-bracket error"),
+            }
+        }
+
+        #[test]
+        fn unmatched_close_bracket_is_a_load_time_error() {
+            let program: ProgramTape<char> = "+]".chars().collect();
+            match Interpreter::new(program, &b""[..], Vec::new()) {
+                Err(err) => assert!(err.contains("Unmatched ']'")),
+                Ok(_) => panic!("expected an unmatched-bracket error"),
+            }
+        }
+
+        #[test]
+        fn tape_supports_moving_left_of_the_origin() {
+            let mut tape = Tape::<u8>::new();
+            tape.seek(-5).unwrap();
+            tape.set(42);
+            assert_eq!(tape.get(), 42);
+            tape.seek(5).unwrap();
+            assert_eq!(tape.get(), 0);
+        }
+
+        #[test]
+        fn bounded_tape_errors_on_overflow_by_default() {
+            let mut tape = Tape::<u8>::with_limit(4);
+            assert!(tape.seek(4).is_err());
+        }
+
+        #[test]
+        #[should_panic(expected = "Tape limit must be at least 1 cell")]
+        fn zero_limit_is_rejected_instead_of_panicking_on_seek() {
+            Tape::<u8>::with_limit_and_policy(0, OverflowPolicy::Wrap);
+        }
+
+        #[test]
+        fn bounded_tape_wraps_when_configured_to() {
+            let mut tape = Tape::<u8>::with_limit_and_policy(4, OverflowPolicy::Wrap);
+            tape.set(9);
+            tape.seek(4).unwrap();
+            assert_eq!(tape.get(), 9);
+        }
+
+        #[test]
+        fn bounded_tape_overflow_surfaces_as_a_step_error_not_a_panic() {
+            let program: ProgramTape<char> = ">".chars().collect();
+            let memory = Tape::<u8>::with_limit(1);
+            let mut interp = Interpreter::with_memory(program, memory, &b""[..], Vec::new()).unwrap();
+            assert!(interp.step().is_err());
         }
-        Ok(())
     }
 }
@@ -0,0 +1,24 @@
+//! Exercises `Interpreter::with_memory` with a bounded `Tape` the way an
+//! external consumer of this crate would: only through `pub` items.
+
+use brainfuck::brainfuck::{Interpreter, OverflowPolicy, StepStatus, Tape};
+
+#[test]
+fn interpreter_with_memory_accepts_a_bounded_tape_from_outside_the_crate() {
+    let program = ">".chars().collect();
+    let memory = Tape::<u8>::with_limit_and_policy(1, OverflowPolicy::Wrap);
+    let mut interp = Interpreter::with_memory(program, memory, &b""[..], Vec::new()).unwrap();
+
+    // A 1-cell tape wrapping back to position 0 should keep running rather
+    // than error or panic.
+    assert_eq!(interp.step().unwrap(), StepStatus::Running);
+}
+
+#[test]
+fn interpreter_with_memory_errors_on_bounded_overflow() {
+    let program = ">".chars().collect();
+    let memory = Tape::<u8>::with_limit(1);
+    let mut interp = Interpreter::with_memory(program, memory, &b""[..], Vec::new()).unwrap();
+
+    assert!(interp.step().is_err());
+}